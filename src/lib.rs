@@ -1,4 +1,4 @@
-use std::{collections::hash_map::DefaultHasher, fs::File, hash::{Hash, Hasher}, io::{self, BufReader}, ops::{Deref, DerefMut}, path::PathBuf};
+use std::{cell::{RefCell, UnsafeCell}, collections::{hash_map::DefaultHasher, HashMap}, hash::{Hash, Hasher}, io, ops::{Deref, DerefMut}, path::PathBuf, rc::Rc, sync::{atomic::{AtomicUsize, Ordering}, Mutex}};
 
 use thiserror::Error;
 use serde::{Serialize, Deserialize};
@@ -8,40 +8,126 @@ pub enum DataReadError{
     #[error("read error")]
     ReadError(#[from] io::Error),
     #[error("serde error error")]
-    SerdeError(#[from] serde_json::Error)
+    SerdeError(#[from] serde_json::Error),
+    #[error("cbor error")]
+    CborError(#[from] serde_cbor::Error)
 }
 
+/// a method run against the watched value when a change is detected, returning whether the
+/// resulting save succeeded.
+type PersistFn<T> = Box<dyn FnMut(&mut T) -> Result<(), DataReadError>>;
+/// the `Send` flavour of [`PersistFn`], required by [`RwSoW`] since its guards may cross threads.
+type SyncPersistFn<T> = Box<dyn FnMut(&mut T) -> Result<(), DataReadError> + Send>;
 
+/// a serialization format `SoW` encodes/decodes the watched value with.
+pub trait Codec {
+    fn encode<T: Serialize>(&self, item: &T) -> Result<Vec<u8>, DataReadError>;
+    fn decode<T: for<'a> Deserialize<'a>>(&self, bytes: &[u8]) -> Result<T, DataReadError>;
+}
+
+/// the default `Codec`, using `serde_json`.
+#[derive(Default, Clone, Copy)]
+pub struct JsonCodec;
+impl Codec for JsonCodec {
+    fn encode<T: Serialize>(&self, item: &T) -> Result<Vec<u8>, DataReadError> {
+        Ok(serde_json::to_vec(item)?)
+    }
+    fn decode<T: for<'a> Deserialize<'a>>(&self, bytes: &[u8]) -> Result<T, DataReadError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// a `Codec` using `serde_cbor`, for users who want a more compact, faster encoding than JSON.
+#[derive(Default, Clone, Copy)]
+pub struct CborCodec;
+impl Codec for CborCodec {
+    fn encode<T: Serialize>(&self, item: &T) -> Result<Vec<u8>, DataReadError> {
+        Ok(serde_cbor::to_vec(item)?)
+    }
+    fn decode<T: for<'a> Deserialize<'a>>(&self, bytes: &[u8]) -> Result<T, DataReadError> {
+        Ok(serde_cbor::from_slice(bytes)?)
+    }
+}
 
-/// listener struct that runs a method when a change was detected, changes are detected using a hash. 
-/// If a value change does not result in a change in the hash the method will not be ran 
+/// a sink/source for the raw bytes `SoW` persists, modeled after rust-lightning's `KVStore`.
+///
+/// implementing this trait lets `SoW` target anything that can store a blob of bytes under a
+/// string key, not just the local filesystem.
+pub trait Store {
+    fn read(&self, key: &str) -> Result<Vec<u8>, DataReadError>;
+    fn write(&mut self, key: &str, bytes: &[u8]) -> Result<(), DataReadError>;
+}
+
+/// the default `Store`, backed by `std::fs`. `key` is interpreted as a filesystem path.
+#[derive(Default)]
+pub struct FileStore;
+impl Store for FileStore {
+    fn read(&self, key: &str) -> Result<Vec<u8>, DataReadError> {
+        Ok(std::fs::read(key)?)
+    }
+    fn write(&mut self, key: &str, bytes: &[u8]) -> Result<(), DataReadError> {
+        std::fs::write(key, bytes)?;
+        Ok(())
+    }
+}
+
+/// an in-memory `Store` backed by a `HashMap<String, Vec<u8>>`, useful for unit-testing
+/// persistence without touching disk (the way rust-lightning's `TestStore` works).
+#[derive(Default)]
+pub struct MemStore {
+    data: HashMap<String, Vec<u8>>
+}
+impl MemStore {
+    pub fn new() -> Self {
+        Self { data: HashMap::new() }
+    }
+}
+impl Store for MemStore {
+    fn read(&self, key: &str) -> Result<Vec<u8>, DataReadError> {
+        self.data.get(key).cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no value stored for key '{key}'")).into()
+        })
+    }
+    fn write(&mut self, key: &str, bytes: &[u8]) -> Result<(), DataReadError> {
+        self.data.insert(key.to_string(), bytes.to_vec());
+        Ok(())
+    }
+}
+
+
+
+/// listener struct that runs every registered method when a change was detected, changes are
+/// detected using a hash. If a value change does not result in a change in the hash none of the
+/// methods will be ran. More than one method can be registered via `add_listener`, e.g. to
+/// persist to disk *and* push a metrics update *and* invalidate a cache off a single watched value.
 ///
 /// ## example
 ///
 /// ```rust
-/// use save_on_write::Listener;
+/// use save_on_write::HashListener;
 /// #[derive(Hash, Debug)]
 /// struct Person {
 ///     name: String,
 ///     age: u8
 /// }
-/// let mut listener = Listener::new(Person{name: "Joe".to_string(), age: 25}, Box::new(|_|{}));
+/// let mut listener = HashListener::new(Person{name: "Joe".to_string(), age: 25}, Box::new(|_| Ok(())));
 /// {
 ///     //making a value mutable does not mean it will detect a change
 ///     let mut lck = listener.lock();
 ///     let a = lck.age;
-///     assert!(lck.detected_change() == false);
+///     assert!(!lck.detected_change());
 /// }
 /// {
 ///     let mut lck = listener.lock();
 ///     lck.age = 20;
-///     assert!(lck.detected_change() == true);
+///     assert!(lck.detected_change());
 /// }
 /// ```
 pub struct HashListener<T: Hash>
 {
     item: T,
-    method: Box<dyn FnMut(&mut T)>
+    methods: Vec<PersistFn<T>>,
+    last_error: Option<DataReadError>,
 }
 
 /// a lock used to detect if a value was changed on drop
@@ -52,10 +138,11 @@ pub struct HashListenerLock<'a, T: Hash>
     hash: u64
 }
 impl<T: Hash> HashListener<T>{
-    pub fn new(item: T, method: Box<dyn FnMut(&mut T)>) -> Self {
+    pub fn new(item: T, method: PersistFn<T>) -> Self {
         Self {
             item,
-            method
+            methods: vec![method],
+            last_error: None,
         }
     }
     pub fn lock(& mut self) -> HashListenerLock<'_, T>{
@@ -67,6 +154,47 @@ impl<T: Hash> HashListener<T>{
             hash: hasher.finish()
         }
     }
+
+    /// register another method to run, in addition to the ones already registered, whenever a
+    /// change is detected. methods run in registration order.
+    pub fn add_listener(&mut self, method: PersistFn<T>) {
+        self.methods.push(method);
+    }
+
+    /// the number of methods currently registered.
+    pub fn listener_count(&self) -> usize {
+        self.methods.len()
+    }
+
+    /// remove all registered methods, e.g. so a watched value stops persisting.
+    pub fn clear_listeners(&mut self) {
+        self.methods.clear();
+    }
+
+    /// take the most recent error produced by an automatic (drop-triggered) save, if any. a later
+    /// successful save does not clear it; only taking it does.
+    pub fn take_error(&mut self) -> Option<DataReadError> {
+        self.last_error.take()
+    }
+
+    /// explicitly run every registered method and observe the result, rather than relying on the
+    /// infallible `Drop` path. if more than one method fails, the last error is returned.
+    pub fn flush(&mut self) -> Result<(), DataReadError> {
+        match self.run_listeners() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn run_listeners(&mut self) -> Option<DataReadError> {
+        let mut last_error = None;
+        for method in &mut self.methods {
+            if let Err(e) = method(&mut self.item) {
+                last_error = Some(e);
+            }
+        }
+        last_error
+    }
 }
 
 impl<'a, T: Hash> HashListenerLock<'a, T>{
@@ -105,79 +233,489 @@ impl<'a, T: Hash> Drop for HashListenerLock<'a, T>{
             return;
         }
 
-        eprintln!("detected change");
-        let method = &mut self.listener.method;
-        method(&mut self.listener.item)
+        if let Some(e) = self.listener.run_listeners() {
+            self.listener.last_error = Some(e);
+        }
     }
 }
 /// write on save
-pub struct SoW<T: Hash>{
+pub struct SoW<T: Hash, S: Store = FileStore, C: Codec = JsonCodec>{
     item: HashListener<T>,
+    store: Rc<RefCell<S>>,
+    codec: C,
 }
-impl<T> SoW<T>
+impl<T, S, C> SoW<T, S, C>
 where
     T: Hash + Serialize,
     T: for<'a> Deserialize<'a>,
+    S: Store + 'static,
+    C: Codec + Clone + 'static,
 {
-    pub fn new_from_file(file: PathBuf) -> Result<Self, DataReadError> {
-        let f = File::open(file.clone())?;
-        let reader = BufReader::new(f);
+    /// load `key` out of `store` (decoded with `codec`) and save back to the same key, in the
+    /// same encoding, on every detected change.
+    pub fn new_from_store_with_codec(store: S, key: impl Into<String>, codec: C) -> Result<Self, DataReadError> {
+        let key = key.into();
+        let store = Rc::new(RefCell::new(store));
 
-        let data: T = serde_json::from_reader(reader)?;
-        let pth = file.clone();
-        let a = move |item: &mut T | {
-            let _ = std::fs::write(&pth, serde_json::to_string(&item).unwrap());
+        let bytes = store.borrow().read(&key)?;
+        let data: T = codec.decode(&bytes)?;
+
+        let persist_store = store.clone();
+        let persist_codec = codec.clone();
+        let a = move |item: &mut T| {
+            let bytes = persist_codec.encode(item)?;
+            let mut store = persist_store.try_borrow_mut().map_err(|_| {
+                io::Error::new(io::ErrorKind::WouldBlock, "store is already borrowed elsewhere")
+            })?;
+            store.write(&key, &bytes)
         };
         let item = HashListener::new(data, Box::new(a));
-        Ok(
-            Self {
-                item
-            }
-        )
+        Ok(Self { item, store, codec })
     }
-    pub fn new_from_item(item: T, dest: PathBuf) -> Result<Self, DataReadError>{
-        let text = serde_json::to_string(&item)?;
-        std::fs::write(&dest, text)?;
-        let pth = dest.clone();
-        let a = move |item: &mut T | {
-            let _ = std::fs::write(&pth, serde_json::to_string(&item).unwrap());
+
+    /// store `item` in `store` under `key` (encoded with `codec`) and save back to the same key,
+    /// in the same encoding, on every detected change.
+    pub fn new_from_item_in_store_with_codec(item: T, store: S, key: impl Into<String>, codec: C) -> Result<Self, DataReadError> {
+        let key = key.into();
+        let bytes = codec.encode(&item)?;
+        let store = Rc::new(RefCell::new(store));
+        store.borrow_mut().write(&key, &bytes)?;
+
+        let persist_store = store.clone();
+        let persist_codec = codec.clone();
+        let a = move |item: &mut T| {
+            let bytes = persist_codec.encode(item)?;
+            let mut store = persist_store.try_borrow_mut().map_err(|_| {
+                io::Error::new(io::ErrorKind::WouldBlock, "store is already borrowed elsewhere")
+            })?;
+            store.write(&key, &bytes)
         };
         let item = HashListener::new(item, Box::new(a));
-        Ok(
-            Self {
-                item
-            }
-        )
+        Ok(Self { item, store, codec })
+    }
+
+    /// access the underlying `Store`, e.g. to read or write keys outside of the watched value.
+    ///
+    /// the persist callback only ever `try_borrow_mut`s the store, so holding a borrow here
+    /// across a mutation of the watched value surfaces as a `DataReadError` via `take_error` (or
+    /// `flush`'s `Result`) instead of panicking.
+    pub fn store(&self) -> Rc<RefCell<S>> {
+        self.store.clone()
+    }
+
+    /// the `Codec` used to encode/decode the watched value.
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+}
+
+impl<T, S> SoW<T, S, JsonCodec>
+where
+    T: Hash + Serialize,
+    T: for<'a> Deserialize<'a>,
+    S: Store + 'static,
+{
+    /// load `key` out of `store` and save back to the same key on every detected change, using JSON.
+    pub fn new_from_store(store: S, key: impl Into<String>) -> Result<Self, DataReadError> {
+        Self::new_from_store_with_codec(store, key, JsonCodec)
+    }
+
+    /// store `item` in `store` under `key` and save back to the same key on every detected change, using JSON.
+    pub fn new_from_item_in_store(item: T, store: S, key: impl Into<String>) -> Result<Self, DataReadError> {
+        Self::new_from_item_in_store_with_codec(item, store, key, JsonCodec)
     }
 }
 
-impl<T: Hash> Deref for SoW<T>{
+impl<T> SoW<T, FileStore, JsonCodec>
+where
+    T: Hash + Serialize,
+    T: for<'a> Deserialize<'a>,
+{
+    pub fn new_from_file(file: PathBuf) -> Result<Self, DataReadError> {
+        Self::new_from_store(FileStore, file.to_string_lossy().into_owned())
+    }
+    pub fn new_from_item(item: T, dest: PathBuf) -> Result<Self, DataReadError>{
+        Self::new_from_item_in_store(item, FileStore, dest.to_string_lossy().into_owned())
+    }
+}
+
+impl<T: Hash, S: Store, C: Codec> Deref for SoW<T, S, C>{
     type Target = HashListener<T>;
     fn deref(&self) -> &Self::Target {
         &self.item
     }
 }
-impl<T: Hash> DerefMut for SoW<T>{
+impl<T: Hash, S: Store, C: Codec> DerefMut for SoW<T, S, C>{
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.item
     }
 }
 
-// TODO: implement a clone listener
+/// the writer bit of `RwSoW`'s state: set while a writer holds the lock.
+const WRITER: usize = 1;
+/// the increment one reader contributes to `RwSoW`'s state.
+const READER: usize = 2;
+
+/// a readers-writer flavoured `SoW`, guarded by an atomic try-lock in the style of the
+/// `try-rwlock` crate, so that read-only access never triggers a hash check or a save.
+///
+/// the low bit of an `AtomicUsize` marks whether a writer holds the lock; the remaining bits
+/// count concurrent readers. `try_read` succeeds only while the writer bit is clear, and hands
+/// out a guard that never hashes the value or runs the persist callback. `try_write` succeeds
+/// only when the state is exactly zero (no readers, no writer), snapshots the hash on acquire,
+/// and on guard drop re-hashes and runs the persist callback if the value changed. This makes
+/// `RwSoW` usable from shared `Arc` contexts where most access is reads.
+pub struct RwSoW<T: Hash> {
+    item: UnsafeCell<T>,
+    state: AtomicUsize,
+    method: UnsafeCell<SyncPersistFn<T>>,
+    last_error: Mutex<Option<DataReadError>>,
+}
+
+// SAFETY: `item` and `method` are only ever accessed through `&T` while readers are present, or
+// through `&mut T` while a writer holds the lock, which `state`'s CAS operations guarantee is
+// exclusive of every other reader and writer. `last_error` is guarded by its own `Mutex`.
+// `try_read` hands out `&T` to multiple threads at once, so `&RwSoW<T>` being `Send` (i.e.
+// `RwSoW<T>: Sync`) requires `T: Sync` too, mirroring `std::sync::RwLock`.
+unsafe impl<T: Hash + Send> Send for RwSoW<T> {}
+unsafe impl<T: Hash + Send + Sync> Sync for RwSoW<T> {}
+
+impl<T: Hash> RwSoW<T> {
+    pub fn new(item: T, method: SyncPersistFn<T>) -> Self {
+        Self {
+            item: UnsafeCell::new(item),
+            state: AtomicUsize::new(0),
+            method: UnsafeCell::new(method),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// acquire a read-only guard if no writer currently holds the lock. readers share access
+    /// freely and never hash the value or run the persist callback.
+    pub fn try_read(&self) -> Option<RwSoWReadGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            if state & WRITER != 0 {
+                return None;
+            }
+            match self.state.compare_exchange_weak(state, state + READER, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return Some(RwSoWReadGuard { lock: self }),
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// acquire an exclusive, save-on-change guard, but only if the lock is completely free (no
+    /// readers and no writer).
+    pub fn try_write(&self) -> Option<RwSoWWriteGuard<'_, T>> {
+        self.state.compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed).ok()?;
+
+        let mut hasher = DefaultHasher::new();
+        // SAFETY: state just transitioned 0 -> WRITER, so we hold exclusive access.
+        unsafe { (*self.item.get()).hash(&mut hasher) };
+        Some(RwSoWWriteGuard { lock: self, hash: hasher.finish() })
+    }
+
+    /// take the most recent error produced by an automatic (write-guard-drop-triggered) save, if
+    /// any. a later successful save does not clear it; only taking it does.
+    pub fn take_error(&self) -> Option<DataReadError> {
+        self.last_error.lock().unwrap().take()
+    }
+}
+
+/// a read-only guard handed out by `RwSoW::try_read`. never hashes the value or fires callbacks.
+pub struct RwSoWReadGuard<'a, T: Hash> {
+    lock: &'a RwSoW<T>,
+}
+impl<'a, T: Hash> Deref for RwSoWReadGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: the writer bit is clear for as long as this guard is alive.
+        unsafe { &*self.lock.item.get() }
+    }
+}
+impl<'a, T: Hash> Drop for RwSoWReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(READER, Ordering::Release);
+    }
+}
+
+/// an exclusive guard handed out by `RwSoW::try_write`. re-hashes the value on drop and runs the
+/// persist callback if it changed.
+pub struct RwSoWWriteGuard<'a, T: Hash> {
+    lock: &'a RwSoW<T>,
+    hash: u64,
+}
+impl<'a, T: Hash> Deref for RwSoWWriteGuard<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: `state` is WRITER for as long as this guard is alive, so access is exclusive.
+        unsafe { &*self.lock.item.get() }
+    }
+}
+impl<'a, T: Hash> DerefMut for RwSoWWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: see `Deref` above.
+        unsafe { &mut *self.lock.item.get() }
+    }
+}
+impl<'a, T: Hash> Drop for RwSoWWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        let mut hasher = DefaultHasher::new();
+        // SAFETY: we still hold exclusive access until `state` is reset below.
+        let (item, method) = unsafe { (&mut *self.lock.item.get(), &mut *self.lock.method.get()) };
+        item.hash(&mut hasher);
+        if hasher.finish() != self.hash {
+            if let Err(e) = method(item) {
+                *self.lock.last_error.lock().unwrap() = Some(e);
+            }
+        }
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
+
+/// a listener that detects changes by comparing a cloned snapshot against the current value with
+/// `PartialEq`, instead of comparing hashes.
+///
+/// `HashListener` can miss a change if it doesn't alter the hash (a hash collision), and can't be
+/// used at all for types that don't implement `Hash` (e.g. most float-bearing structs).
+/// `CloneListener` gives exact change detection for `Clone + PartialEq` types at the cost of a
+/// clone per `lock()`.
+pub struct CloneListener<T: Clone + PartialEq>
+{
+    item: T,
+    methods: Vec<PersistFn<T>>,
+    last_error: Option<DataReadError>,
+}
+
+/// a lock used to detect if a value was changed on drop, by comparing against a cloned snapshot.
+pub struct CloneListenerLock<'a, T: Clone + PartialEq>
+{
+    listener: &'a mut CloneListener<T>,
+    possible_change: bool,
+    snapshot: T,
+}
+impl<T: Clone + PartialEq> CloneListener<T>{
+    pub fn new(item: T, method: PersistFn<T>) -> Self {
+        Self {
+            item,
+            methods: vec![method],
+            last_error: None,
+        }
+    }
+    pub fn lock(&mut self) -> CloneListenerLock<'_, T>{
+        let snapshot = self.item.clone();
+        CloneListenerLock {
+            listener: self,
+            possible_change: false,
+            snapshot
+        }
+    }
+
+    /// register another method to run, in addition to the ones already registered, whenever a
+    /// change is detected. methods run in registration order.
+    pub fn add_listener(&mut self, method: PersistFn<T>) {
+        self.methods.push(method);
+    }
+
+    /// the number of methods currently registered.
+    pub fn listener_count(&self) -> usize {
+        self.methods.len()
+    }
+
+    /// remove all registered methods.
+    pub fn clear_listeners(&mut self) {
+        self.methods.clear();
+    }
+
+    /// take the most recent error produced by an automatic (drop-triggered) save, if any. a later
+    /// successful save does not clear it; only taking it does.
+    pub fn take_error(&mut self) -> Option<DataReadError> {
+        self.last_error.take()
+    }
+
+    /// explicitly run every registered method and observe the result, rather than relying on the
+    /// infallible `Drop` path. if more than one method fails, the last error is returned.
+    pub fn flush(&mut self) -> Result<(), DataReadError> {
+        match self.run_listeners() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn run_listeners(&mut self) -> Option<DataReadError> {
+        let mut last_error = None;
+        for method in &mut self.methods {
+            if let Err(e) = method(&mut self.item) {
+                last_error = Some(e);
+            }
+        }
+        last_error
+    }
+}
+
+impl<'a, T: Clone + PartialEq> CloneListenerLock<'a, T>{
+    #[allow(unused)]
+    pub(crate) fn detected_possible_change(&self) -> bool {
+        self.possible_change
+    }
+    pub fn detected_change(&self) -> bool {
+        if !self.possible_change {
+            return false;
+        }
+        self.listener.item != self.snapshot
+    }
+}
+
+impl<'a, T: Clone + PartialEq> Deref for CloneListenerLock<'a, T>{
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.listener.item
+    }
+}
+impl<'a, T: Clone + PartialEq> DerefMut for CloneListenerLock<'a, T>{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.possible_change = true;
+        &mut self.listener.item
+    }
+}
+
+impl<'a, T: Clone + PartialEq> Drop for CloneListenerLock<'a, T>{
+    fn drop(&mut self) {
+        if !self.detected_change(){
+            return;
+        }
+
+        if let Some(e) = self.listener.run_listeners() {
+            self.listener.last_error = Some(e);
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests{
-    use crate::HashListener;
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::{atomic::AtomicU32, Arc};
 
     #[test]
     pub fn has_changed(){
         let a = 5;
-        let mut notifier = HashListener::new(a, Box::new(|b| {}));
+        let mut notifier = HashListener::new(a, Box::new(|_| Ok(())));
         {
             #[allow(unused_mut)]
             let mut b = notifier.lock();
             let _ = *b + 5;
-            assert!(b.detected_change() == false);
+            assert!(!b.detected_change());
+        }
+    }
+
+    #[derive(Hash, Serialize, Deserialize, PartialEq, Debug)]
+    struct Counter { n: u32 }
+
+    #[test]
+    fn mem_store_codec_round_trip() {
+        let mut sow = SoW::<Counter, MemStore, CborCodec>::new_from_item_in_store_with_codec(
+            Counter { n: 1 },
+            MemStore::new(),
+            "counter",
+            CborCodec,
+        ).unwrap();
+        sow.lock().n = 2;
+
+        let bytes = sow.store().borrow().read("counter").unwrap();
+        let decoded: Counter = serde_cbor::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, Counter { n: 2 });
+    }
+
+    /// a `Store` whose `write` always fails, to exercise the error-reporting paths.
+    struct FailingStore;
+    impl Store for FailingStore {
+        fn read(&self, _key: &str) -> Result<Vec<u8>, DataReadError> {
+            Ok(serde_json::to_vec(&Counter { n: 1 }).unwrap())
+        }
+        fn write(&mut self, _key: &str, _bytes: &[u8]) -> Result<(), DataReadError> {
+            Err(io::Error::other("write always fails").into())
+        }
+    }
+
+    #[test]
+    fn flush_and_take_error_report_store_failures() {
+        let mut sow = SoW::<Counter, FailingStore>::new_from_store(FailingStore, "counter").unwrap();
+
+        assert!(sow.take_error().is_none());
+        sow.lock().n = 2;
+        assert!(sow.take_error().is_some());
+        assert!(sow.take_error().is_none(), "take_error should clear after reading it once");
+
+        assert!(sow.flush().is_err());
+    }
+
+    /// a type whose `Hash` impl is intentionally constant, simulating a hash collision between
+    /// two distinct values. `HashListener` would miss a change here; `CloneListener` must not.
+    #[derive(Clone, PartialEq, Debug)]
+    struct CollidingHash(i32);
+    impl Hash for CollidingHash {
+        fn hash<H: Hasher>(&self, _state: &mut H) {}
+    }
+
+    #[test]
+    fn clone_listener_detects_change_that_leaves_the_hash_unchanged() {
+        let calls = Rc::new(Cell::new(0));
+        let callback_calls = calls.clone();
+        let mut listener = CloneListener::new(
+            CollidingHash(1),
+            Box::new(move |_| {
+                callback_calls.set(callback_calls.get() + 1);
+                Ok(())
+            }),
+        );
+        {
+            let mut lock = listener.lock();
+            lock.0 = 2;
+        }
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn rwsow_readers_and_writer_are_mutually_exclusive() {
+        let rw = RwSoW::new(Counter { n: 1 }, Box::new(|_: &mut Counter| Ok(())));
+
+        let r1 = rw.try_read().unwrap();
+        let r2 = rw.try_read().unwrap();
+        assert!(rw.try_write().is_none(), "a writer must not acquire while readers are live");
+        drop((r1, r2));
+
+        let w = rw.try_write().unwrap();
+        assert!(rw.try_read().is_none(), "a reader must not acquire while a writer is live");
+        assert!(rw.try_write().is_none(), "a second writer must not acquire while one is live");
+        drop(w);
+
+        assert!(rw.try_read().is_some());
+    }
+
+    #[test]
+    fn rwsow_fires_persist_callback_only_on_change() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let callback_calls = calls.clone();
+        let rw = RwSoW::new(
+            Counter { n: 1 },
+            Box::new(move |_: &mut Counter| {
+                callback_calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }),
+        );
+
+        {
+            let w = rw.try_write().unwrap();
+            let _ = w.n;
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 0, "reading through a write guard without mutating must not fire");
+
+        {
+            let mut w = rw.try_write().unwrap();
+            w.n = 2;
         }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
     }
 }
\ No newline at end of file